@@ -0,0 +1,399 @@
+//! Language metadata and code/header highlighting
+//!
+//! `Language` records the display metadata the-way associates with each supported language.
+//! `CodeHighlight` owns the syntect state used to render snippet headers and code, and, when
+//! configured, delegates code highlighting to a tree-sitter backend first, falling back to
+//! syntect per-snippet whenever no grammar is installed for that language.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Color, FontStyle, Style, Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::as_24_bit_terminal_escaped;
+
+use crate::the_way::config::Config;
+use crate::the_way::injection::markdown_fence_injections;
+use crate::the_way::theme::HexColor;
+use crate::the_way::ts_highlight::{
+    capture_to_style_category, HighlightBackend, HighlightSpan, TreeSitterHighlighter,
+};
+
+/// Converts a parsed `#RRGGBB[AA]` theme color into syntect's `Color`
+fn hex_color_to_color(hex: HexColor) -> Color {
+    Color {
+        r: hex.r(),
+        g: hex.g(),
+        b: hex.b(),
+        a: hex.a(),
+    }
+}
+
+/// Metadata the-way associates with a supported language
+#[derive(Debug, Clone)]
+pub(crate) struct Language {
+    pub(crate) extension: String,
+    pub(crate) color: Color,
+}
+
+impl Language {
+    /// Looks up `language_name`'s extension, defaulting to an empty string for unknown languages
+    pub(crate) fn get_extension(
+        language_name: &str,
+        languages: &HashMap<String, Language>,
+    ) -> String {
+        languages
+            .get(language_name)
+            .map(|language| language.extension.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Renders snippet headers (`main_style`/`accent_style`/`tag_style`) and code. Code highlighting
+/// goes through the tree-sitter backend first when `HighlightBackend::TreeSitter` is configured,
+/// with a per-snippet fallback to syntect whenever no grammar is installed for the language.
+pub(crate) struct CodeHighlight {
+    pub(crate) main_style: Style,
+    pub(crate) accent_style: Style,
+    pub(crate) tag_style: Style,
+    syntax_set: SyntaxSet,
+    theme: Theme,
+    backend: HighlightBackend,
+    tree_sitter: Option<RefCell<TreeSitterHighlighter>>,
+    language_colors: HashMap<String, Color>,
+}
+
+impl CodeHighlight {
+    /// Builds a highlighter from the built-in syntect theme, applying `config.theme`'s color
+    /// overrides and selecting `config.highlight_backend` as the code highlighting strategy
+    pub(crate) fn new(config: &Config) -> color_eyre::Result<Self> {
+        let theme_set = ThemeSet::load_defaults();
+        let theme = theme_set
+            .themes
+            .get("base16-ocean.dark")
+            .ok_or_else(|| color_eyre::eyre::eyre!("missing built-in theme base16-ocean.dark"))?
+            .clone();
+        let default_color = theme.settings.foreground.unwrap_or(Color::WHITE);
+        let style_for = |override_color: Option<HexColor>| Style {
+            foreground: override_color.map_or(default_color, hex_color_to_color),
+            background: Color::BLACK,
+            font_style: FontStyle::empty(),
+        };
+
+        Ok(Self {
+            main_style: style_for(config.theme.main_color),
+            accent_style: style_for(config.theme.accent_color),
+            tag_style: style_for(config.theme.tag_color),
+            syntax_set: SyntaxSet::load_defaults_newlines(),
+            theme,
+            backend: config.highlight_backend,
+            tree_sitter: config
+                .grammar_dir
+                .clone()
+                .map(|grammar_dir| RefCell::new(TreeSitterHighlighter::new(grammar_dir))),
+            language_colors: config
+                .theme
+                .language_colors
+                .iter()
+                .map(|(name, color)| (name.clone(), hex_color_to_color(*color)))
+                .collect(),
+        })
+    }
+
+    /// Colors a single glyph (`utils::BOX`) in `color`, used as the language-identifying block in
+    /// a snippet header
+    pub(crate) fn highlight_block(color: Color) -> color_eyre::Result<String> {
+        let style = Style {
+            foreground: color,
+            background: Color::BLACK,
+            font_style: FontStyle::empty(),
+        };
+        Ok(Self::highlight_string(crate::utils::BOX, style))
+    }
+
+    /// Returns the theme's override color for `language_name`, if the user configured one,
+    /// otherwise `default` (normally that language's own `Language::color`)
+    pub(crate) fn block_color(&self, language_name: &str, default: Color) -> Color {
+        self.language_colors
+            .get(language_name)
+            .copied()
+            .unwrap_or(default)
+    }
+
+    /// Colors `text` with a single `style`, with no trailing reset (callers push
+    /// `utils::END_ANSI` once at the end of the whole colorized run)
+    pub(crate) fn highlight_string(text: &str, style: Style) -> String {
+        as_24_bit_terminal_escaped(&[(style, text)], false)
+    }
+
+    /// Highlights `code` (a snippet's own `extension`), trying the tree-sitter backend first when
+    /// configured and falling back to syntect when no grammar/query is installed for `extension`
+    pub(crate) fn highlight_code(
+        &self,
+        code: &str,
+        extension: &str,
+    ) -> color_eyre::Result<Vec<String>> {
+        if self.backend == HighlightBackend::TreeSitter {
+            if let Some(tree_sitter) = &self.tree_sitter {
+                if let Some(spans) = crate::the_way::injection::highlight_extension_with_injections(
+                    &mut tree_sitter.borrow_mut(),
+                    code,
+                    extension,
+                )? {
+                    return Ok(self.render_spans(code, &spans));
+                }
+            }
+        }
+        self.highlight_code_syntect_with_fence_injections(code, extension)
+    }
+
+    /// Converts tree-sitter `HighlightSpan`s into colorized ANSI fragments. Spans routinely nest
+    /// (e.g. an `@string.escape` inside an `@string`, standard in most `highlights.scm` files), so
+    /// this sweeps start/end boundaries left to right and colors each stretch of text by whichever
+    /// span is innermost at that point, rather than assuming spans are disjoint.
+    fn render_spans(&self, code: &str, spans: &[HighlightSpan]) -> Vec<String> {
+        enum Edge {
+            Start(usize),
+            End(usize),
+        }
+
+        let mut events: Vec<(usize, Edge)> = Vec::with_capacity(spans.len() * 2);
+        for (i, span) in spans.iter().enumerate() {
+            if span.range.is_empty() {
+                continue;
+            }
+            events.push((span.range.start, Edge::Start(i)));
+            events.push((span.range.end, Edge::End(i)));
+        }
+        // At equal positions, close before opening, and open the widest (outermost) span first so
+        // a narrower, simultaneously-starting span ends up on top of the stack.
+        events.sort_by_key(|(pos, edge)| match edge {
+            Edge::End(_) => (*pos, 0, 0),
+            Edge::Start(i) => (
+                *pos,
+                1,
+                usize::MAX - (spans[*i].range.end - spans[*i].range.start),
+            ),
+        });
+
+        let mut colorized = Vec::new();
+        let mut stack: Vec<usize> = Vec::new();
+        let mut cursor = 0;
+        for (pos, edge) in events {
+            if pos > cursor {
+                let text = &code[cursor..pos];
+                colorized.push(match stack.last() {
+                    Some(&i) => {
+                        let style =
+                            self.category_style(capture_to_style_category(&spans[i].category));
+                        Self::highlight_string(text, style)
+                    }
+                    None => text.to_owned(),
+                });
+                cursor = pos;
+            }
+            match edge {
+                Edge::Start(i) => stack.push(i),
+                Edge::End(i) => stack.retain(|&s| s != i),
+            }
+        }
+        if cursor < code.len() {
+            colorized.push(code[cursor..].to_owned());
+        }
+        colorized
+    }
+
+    /// Maps a coarse capture category to a style for the tree-sitter backend. The colors are a
+    /// fixed built-in palette and are not looked up in the syntect theme, so the same token can be
+    /// colored differently by the two backends; unlisted categories use the theme's foreground.
+    fn category_style(&self, category: &str) -> Style {
+        let foreground = match category {
+            "keyword" => Color {
+                r: 0xf9,
+                g: 0x26,
+                b: 0x72,
+                a: 0xff,
+            },
+            "function" => Color {
+                r: 0xa6,
+                g: 0xe2,
+                b: 0x2e,
+                a: 0xff,
+            },
+            "string" => Color {
+                r: 0xe6,
+                g: 0xdb,
+                b: 0x74,
+                a: 0xff,
+            },
+            "comment" => Color {
+                r: 0x75,
+                g: 0x71,
+                b: 0x5e,
+                a: 0xff,
+            },
+            "type" => Color {
+                r: 0x66,
+                g: 0xd9,
+                b: 0xef,
+                a: 0xff,
+            },
+            _ => self.theme.settings.foreground.unwrap_or(Color::WHITE),
+        };
+        Style {
+            foreground,
+            background: Color::BLACK,
+            font_style: FontStyle::empty(),
+        }
+    }
+
+    /// syntect has no query mechanism for sub-language regions, so the only injection it
+    /// understands is the built-in Markdown-fence rule: each fence is highlighted with its own
+    /// syntax definition and spliced back in around the plain Markdown highlight of the rest.
+    fn highlight_code_syntect_with_fence_injections(
+        &self,
+        code: &str,
+        extension: &str,
+    ) -> color_eyre::Result<Vec<String>> {
+        if extension != "md" && extension != "markdown" {
+            return self.highlight_code_syntect(code, extension);
+        }
+        let injections = markdown_fence_injections(code);
+        if injections.is_empty() {
+            return self.highlight_code_syntect(code, extension);
+        }
+
+        let mut colorized = Vec::new();
+        let mut cursor = 0;
+        for injection in &injections {
+            if injection.range.start > cursor {
+                colorized.extend(
+                    self.highlight_code_syntect(&code[cursor..injection.range.start], extension)?,
+                );
+            }
+            colorized.extend(
+                self.highlight_code_syntect(&code[injection.range.clone()], &injection.language)?,
+            );
+            cursor = injection.range.end;
+        }
+        if cursor < code.len() {
+            colorized.extend(self.highlight_code_syntect(&code[cursor..], extension)?);
+        }
+        Ok(colorized)
+    }
+
+    /// Highlights `code` with syntect, resolving its syntax definition from `token` (a file
+    /// extension like `rs` or a language name like `rust`) and falling back to plain text when
+    /// neither is recognized
+    fn highlight_code_syntect(&self, code: &str, token: &str) -> color_eyre::Result<Vec<String>> {
+        let syntax = self
+            .syntax_set
+            .find_syntax_by_token(token)
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        let mut colorized = Vec::new();
+        for line in code.lines() {
+            let ranges = highlighter.highlight_line(line, &self.syntax_set)?;
+            colorized.push(as_24_bit_terminal_escaped(&ranges, false));
+            colorized.push(String::from("\n"));
+        }
+        Ok(colorized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::the_way::theme::HexColor;
+
+    fn strip_ansi(text: &str) -> String {
+        let mut out = String::new();
+        let mut in_escape = false;
+        for ch in text.chars() {
+            match (in_escape, ch) {
+                (false, '\x1b') => in_escape = true,
+                (true, 'm') => in_escape = false,
+                (true, _) => {}
+                (false, _) => out.push(ch),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn applies_a_configured_main_color_override() {
+        let mut config = Config::default();
+        config.theme.main_color = Some(HexColor::parse("#ff0000").unwrap());
+        let highlighter = CodeHighlight::new(&config).unwrap();
+        assert_eq!(
+            highlighter.main_style.foreground,
+            hex_color_to_color(config.theme.main_color.unwrap())
+        );
+    }
+
+    #[test]
+    fn block_color_prefers_a_language_override_over_the_default() {
+        let mut config = Config::default();
+        config
+            .theme
+            .language_colors
+            .insert("rust".to_owned(), HexColor::parse("#00ff00").unwrap());
+        let highlighter = CodeHighlight::new(&config).unwrap();
+        let default = Color {
+            r: 1,
+            g: 2,
+            b: 3,
+            a: 0xff,
+        };
+        assert_eq!(
+            highlighter.block_color("rust", default),
+            hex_color_to_color(config.theme.language_colors["rust"])
+        );
+        assert_eq!(highlighter.block_color("python", default), default);
+    }
+
+    #[test]
+    fn render_spans_colors_a_single_span_and_leaves_surrounding_text_plain() {
+        let highlighter = CodeHighlight::new(&Config::default()).unwrap();
+        let code = "let x = 1;";
+        let spans = vec![HighlightSpan {
+            range: 4..5,
+            category: "variable".to_owned(),
+        }];
+        let fragments = highlighter.render_spans(code, &spans);
+        assert_eq!(fragments[0], "let ");
+        assert!(fragments[1].contains('x'));
+        assert_eq!(fragments[2], " = 1;");
+    }
+
+    #[test]
+    fn render_spans_resolves_a_nested_capture_without_duplicating_text() {
+        let highlighter = CodeHighlight::new(&Config::default()).unwrap();
+        let code = "\"a\\nb\"";
+        let spans = vec![
+            HighlightSpan {
+                range: 0..code.len(),
+                category: "string".to_owned(),
+            },
+            HighlightSpan {
+                range: 2..4,
+                category: "string.escape".to_owned(),
+            },
+        ];
+        let fragments = highlighter.render_spans(code, &spans);
+        let rendered: String = fragments.iter().map(|f| strip_ansi(f)).collect();
+        assert_eq!(rendered, code);
+    }
+
+    #[test]
+    fn splices_a_markdown_fence_with_its_own_language_highlighting() {
+        let highlighter = CodeHighlight::new(&Config::default()).unwrap();
+        let code = "before\n```rust\nfn x() {}\n```\nafter\n";
+        let fragments = highlighter
+            .highlight_code_syntect_with_fence_injections(code, "md")
+            .unwrap();
+        let rendered: String = fragments.iter().map(|f| strip_ansi(f)).collect();
+        assert_eq!(rendered, code);
+    }
+}
@@ -0,0 +1,27 @@
+//! User-facing configuration for snippet highlighting and sync
+//!
+//! Parsed from the-way's config file; fields default such that an empty/missing config section
+//! reproduces today's behavior (syntect highlighting, no theme overrides, sync disabled).
+
+use std::path::PathBuf;
+
+use crate::the_way::sync::SyncConfig;
+use crate::the_way::theme::ThemeConfig;
+use crate::the_way::ts_highlight::HighlightBackend;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct Config {
+    /// Which backend `CodeHighlight` uses to highlight snippet code
+    #[serde(default)]
+    pub(crate) highlight_backend: HighlightBackend,
+    /// Directory `TreeSitterHighlighter` resolves grammar libraries from; tree-sitter highlighting
+    /// is unavailable (falls back to syntect for every snippet) when unset
+    #[serde(default)]
+    pub(crate) grammar_dir: Option<PathBuf>,
+    /// Color overrides for `CodeHighlight`'s header styles and per-language block colors
+    #[serde(default)]
+    pub(crate) theme: ThemeConfig,
+    /// Remote endpoint and cache settings for `the-way sync`; sync is disabled when unset
+    #[serde(default)]
+    pub(crate) sync: Option<SyncConfig>,
+}
@@ -0,0 +1,153 @@
+//! Embedded-language region detection and splicing for the tree-sitter highlight backend
+
+use std::ops::Range;
+
+use crate::the_way::ts_highlight::{HighlightSpan, TreeSitterHighlighter};
+
+/// Detects injections for `extension` (currently: Markdown fences) and re-highlights `code`
+/// through the tree-sitter backend with them applied. Returns `Ok(None)` when the outer language
+/// itself has no grammar installed, so `CodeHighlight` can fall back to syntect for the whole
+/// snippet, exactly as it would with no injections involved.
+pub(crate) fn highlight_extension_with_injections(
+    highlighter: &mut TreeSitterHighlighter,
+    code: &str,
+    extension: &str,
+) -> color_eyre::Result<Option<Vec<HighlightSpan>>> {
+    let injections = match extension {
+        "md" | "markdown" => markdown_fence_injections(code),
+        _ => Vec::new(),
+    };
+    highlight_with_injections(highlighter, code, extension, &injections)
+}
+
+/// A byte range of `code` that should be re-highlighted as `language` instead of the snippet's
+/// own outer language
+pub(crate) struct Injection {
+    pub(crate) range: Range<usize>,
+    pub(crate) language: String,
+}
+
+/// Detects fenced code blocks in a Markdown snippet (` ```lang ... ``` `) and returns one
+/// `Injection` per fence whose info string names a language, covering the fence's inner byte range
+pub(crate) fn markdown_fence_injections(code: &str) -> Vec<Injection> {
+    let mut injections = Vec::new();
+    let mut search_from = 0;
+    while let Some(start) = code[search_from..].find("```") {
+        let fence_start = search_from + start;
+        let Some(line_end) = code[fence_start..].find('\n') else {
+            break;
+        };
+        let info = code[fence_start + 3..fence_start + line_end].trim();
+        let content_start = fence_start + line_end + 1;
+        let Some(end_rel) = code[content_start..].find("```") else {
+            break;
+        };
+        let content_end = content_start + end_rel;
+        if !info.is_empty() {
+            injections.push(Injection {
+                range: content_start..content_end,
+                language: info.to_owned(),
+            });
+        }
+        search_from = content_end + 3;
+    }
+    injections
+}
+
+/// Highlights `code` in `outer_language`, then re-highlights each `injection` region in its own
+/// language and splices the result back in, producing a single span list sorted by start offset in
+/// which no outer span overlaps an injection region (spans may still nest within either layer).
+/// Injection ranges are assumed already sorted and non-overlapping (true for
+/// `markdown_fence_injections`, since fences cannot nest).
+///
+/// Returns `Ok(None)`, same as `TreeSitterHighlighter::highlight`, when `outer_language` itself has
+/// no grammar installed - that's a whole-snippet fallback decision for the caller, distinct from an
+/// individual injection's language being unavailable (which only drops that one region's
+/// recoloring, see `highlight_injection`).
+pub(crate) fn highlight_with_injections(
+    highlighter: &mut TreeSitterHighlighter,
+    code: &str,
+    outer_language: &str,
+    injections: &[Injection],
+) -> color_eyre::Result<Option<Vec<HighlightSpan>>> {
+    let Some(outer_spans) = highlighter.highlight(code, outer_language)? else {
+        return Ok(None);
+    };
+    if injections.is_empty() {
+        return Ok(Some(outer_spans));
+    }
+
+    let mut spans = Vec::new();
+    let mut injection_idx = 0;
+    for span in outer_spans {
+        while injection_idx < injections.len()
+            && injections[injection_idx].range.end <= span.range.start
+        {
+            spans.extend(highlight_injection(
+                highlighter,
+                code,
+                &injections[injection_idx],
+            )?);
+            injection_idx += 1;
+        }
+        if let Some(injection) = injections.get(injection_idx) {
+            if span.range.start < injection.range.end && span.range.end > injection.range.start {
+                // This outer token falls inside the current injection region; the inner
+                // highlighter owns that byte range instead, so drop the outer token.
+                continue;
+            }
+        }
+        spans.push(span);
+    }
+    while injection_idx < injections.len() {
+        spans.extend(highlight_injection(
+            highlighter,
+            code,
+            &injections[injection_idx],
+        )?);
+        injection_idx += 1;
+    }
+
+    spans.sort_by_key(|span| span.range.start);
+    Ok(Some(spans))
+}
+
+/// Re-highlights a single injection region with its own language, offsetting the resulting
+/// spans' byte ranges back into the outer snippet's coordinate space
+fn highlight_injection(
+    highlighter: &mut TreeSitterHighlighter,
+    code: &str,
+    injection: &Injection,
+) -> color_eyre::Result<Vec<HighlightSpan>> {
+    let inner_code = &code[injection.range.clone()];
+    let inner_spans = highlighter
+        .highlight(inner_code, &injection.language)?
+        .unwrap_or_default();
+    Ok(inner_spans
+        .into_iter()
+        .map(|span| HighlightSpan {
+            range: (span.range.start + injection.range.start)
+                ..(span.range.end + injection.range.start),
+            category: span.category,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_a_fenced_block_naming_a_language_but_skips_an_unlabeled_one() {
+        let code = "intro\n```rust\nfn main() {}\n```\noutro\n```\nplain\n```\n";
+        let injections = markdown_fence_injections(code);
+        assert_eq!(injections.len(), 1);
+        assert_eq!(injections[0].language, "rust");
+        assert_eq!(&code[injections[0].range.clone()], "fn main() {}\n");
+    }
+
+    #[test]
+    fn finds_no_injections_without_a_closing_fence() {
+        assert!(markdown_fence_injections("```rust\nfn main() {}\n").is_empty());
+    }
+}
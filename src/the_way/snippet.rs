@@ -8,7 +8,7 @@ use crate::language::{CodeHighlight, Language};
 use crate::utils;
 
 /// Stores information about a quote
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Snippet {
     /// Snippet index, used to retrieve, copy, or modify a snippet
     #[serde(default)]
@@ -186,7 +186,8 @@ impl Snippet {
         language: &Language,
     ) -> color_eyre::Result<Vec<String>> {
         let mut colorized = Vec::new();
-        let block = CodeHighlight::highlight_block(language.color)?;
+        let block_color = highlighter.block_color(&self.language, language.color);
+        let block = CodeHighlight::highlight_block(block_color)?;
         colorized.push(block);
         let text = format!("#{}. {} ", self.index, self.description);
         colorized.push(CodeHighlight::highlight_string(
@@ -232,4 +233,165 @@ impl Snippet {
         colorized.push(String::from("\n"));
         Ok(colorized)
     }
+
+    /// Renders the snippet as HTML with inline `style="color:#..."` spans, reusing
+    /// `pretty_print_header`/`pretty_print_code` and converting their ANSI escape codes to markup
+    /// instead of re-deriving the colors from scratch.
+    ///
+    /// With `standalone` set, the fragment is wrapped in a full HTML document with its own
+    /// `<style>` block; otherwise a self-contained `<pre><code>` fragment is returned, ready to be
+    /// pasted into an existing page. With `rainbow` set, each highlighted span is recolored with a
+    /// stable pseudo-random hue derived from its own text instead of the syntect theme color, which
+    /// makes repeated identifiers easy to tell apart at a glance.
+    pub(crate) fn to_html(
+        &self,
+        highlighter: &CodeHighlight,
+        language: &Language,
+        standalone: bool,
+        rainbow: bool,
+    ) -> color_eyre::Result<String> {
+        let header = ansi_to_html(&self.pretty_print_header(highlighter, language)?, rainbow);
+        let code = ansi_to_html(&self.pretty_print_code(highlighter)?, rainbow);
+        let fragment = format!("<pre><code>{}\n{}</code></pre>", header, code);
+        if standalone {
+            Ok(format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\nbody {{ background: #272822; }}\npre {{ padding: 1em; overflow-x: auto; }}\ncode {{ font-family: monospace; white-space: pre; }}\n</style>\n</head>\n<body>\n{}\n</body>\n</html>\n",
+                escape_html(&self.description),
+                fragment
+            ))
+        } else {
+            Ok(fragment)
+        }
+    }
+}
+
+/// Escapes HTML-special characters so untrusted snippet text can be safely embedded in markup
+fn escape_html(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut escaped, c| {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                _ => escaped.push(c),
+            }
+            escaped
+        })
+}
+
+/// Converts a sequence of ANSI truecolor-escaped fragments (as produced by `pretty_print_header`
+/// and `pretty_print_code`) into HTML, turning each `ESC[38;2;r;g;bm` foreground code into a
+/// `<span style="color:#rrggbb">` and each reset (`utils::END_ANSI`) into a closing `</span>`.
+/// If `rainbow` is set, the parsed `r,g,b` is discarded in favor of a stable hash-derived color
+/// for the span's own text.
+fn ansi_to_html(colorized: &[String], rainbow: bool) -> String {
+    let mut html = String::new();
+    let mut span_open = false;
+    for fragment in colorized {
+        let mut rest = fragment.as_str();
+        while let Some(start) = rest.find("\x1b[") {
+            if start > 0 {
+                write_span_text(&mut html, &rest[..start], rainbow, span_open);
+            }
+            let Some(end) = rest[start..].find('m') else {
+                break;
+            };
+            let code = &rest[start + 2..start + end];
+            if span_open {
+                html.push_str("</span>");
+                span_open = false;
+            }
+            if let Some(rgb) = parse_truecolor(code) {
+                if !rainbow {
+                    html.push_str(&format!(
+                        "<span style=\"color:#{:02x}{:02x}{:02x}\">",
+                        rgb.0, rgb.1, rgb.2
+                    ));
+                    span_open = true;
+                }
+            }
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            write_span_text(&mut html, rest, rainbow, span_open);
+        }
+    }
+    if span_open {
+        html.push_str("</span>");
+    }
+    html
+}
+
+/// Writes `text` (HTML-escaped) into `html`, wrapping it in its own rainbow-colored span when
+/// `rainbow` is set; otherwise the text is appended as-is, inheriting any span already open
+fn write_span_text(html: &mut String, text: &str, rainbow: bool, span_already_open: bool) {
+    if rainbow && !span_already_open && !text.trim().is_empty() {
+        html.push_str(&format!(
+            "<span style=\"color:{}\">{}</span>",
+            rainbow_hsl(text),
+            escape_html(text)
+        ));
+    } else {
+        html.push_str(&escape_html(text));
+    }
+}
+
+/// Parses the truecolor foreground component (`38;2;r;g;b`) out of an SGR code, wherever it
+/// appears among the code's `;`-separated parameters. This tolerates sequences that combine an
+/// attribute with the color in one escape, e.g. `1;38;2;r;g;b` for a bold, colored style.
+fn parse_truecolor(code: &str) -> Option<(u8, u8, u8)> {
+    let params: Vec<&str> = code.split(';').collect();
+    let start = params.iter().position(|&param| param == "38")?;
+    if *params.get(start + 1)? != "2" {
+        return None;
+    }
+    let r = params.get(start + 2)?.parse().ok()?;
+    let g = params.get(start + 3)?.parse().ok()?;
+    let b = params.get(start + 4)?.parse().ok()?;
+    Some((r, g, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_html_special_characters() {
+        assert_eq!(escape_html("<a>&\"'"), "&lt;a&gt;&amp;&quot;&#39;");
+    }
+
+    #[test]
+    fn parses_truecolor_wherever_it_appears_in_the_sgr_code() {
+        assert_eq!(parse_truecolor("38;2;10;20;30"), Some((10, 20, 30)));
+        assert_eq!(parse_truecolor("1;38;2;200;100;50"), Some((200, 100, 50)));
+        assert_eq!(parse_truecolor("0"), None);
+    }
+
+    #[test]
+    fn converts_a_bold_truecolor_style_to_a_single_span() {
+        let fragment = String::from("\x1b[1;38;2;200;100;50mHello\x1b[0m");
+        let html = ansi_to_html(&[fragment], false);
+        assert_eq!(html, "<span style=\"color:#c86432\">Hello</span>");
+    }
+
+    #[test]
+    fn rainbow_color_is_stable_for_the_same_text() {
+        assert_eq!(rainbow_hsl("foo"), rainbow_hsl("foo"));
+    }
+}
+
+/// Derives a stable pseudo-random `hsl(h,s%,l%)` color from `seed`, with `h` in `0..361` and `s`
+/// in `42..99`, useful for telling repeated identifiers apart at a glance
+fn rainbow_hsl(seed: &str) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    let hash = hasher.finish();
+    let h = hash % 361;
+    let s = 42 + (hash / 361) % 57;
+    format!("hsl({},{}%,60%)", h, s)
 }
@@ -0,0 +1,257 @@
+//! Remote snippet sync: push/pull against a configured endpoint, with a local cache and
+//! last-writer-wins merge that reports rather than silently resolves conflicts
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::the_way::snippet::Snippet;
+use crate::utils;
+
+/// Where a merge couldn't be resolved automatically: the same snippet changed on both sides since
+/// the last sync
+pub(crate) struct SyncConflict {
+    pub(crate) index: usize,
+    pub(crate) local: Snippet,
+    pub(crate) remote: Snippet,
+}
+
+/// Result of merging a local and remote snippet collection
+pub(crate) struct MergeOutcome {
+    pub(crate) merged: Vec<Snippet>,
+    pub(crate) conflicts: Vec<SyncConflict>,
+}
+
+/// Endpoint and caching configuration for snippet sync, read from the config file's `sync`
+/// section (absent by default, so sync is opt-in)
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct SyncConfig {
+    pub(crate) endpoint: String,
+    pub(crate) cache_path: PathBuf,
+    /// Seconds the cached pull stays fresh before `fetch_remote` hits the network again
+    pub(crate) cache_ttl_secs: u64,
+}
+
+impl SyncConfig {
+    fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl_secs)
+    }
+}
+
+impl SyncConfig {
+    /// Serializes `snippets` to the newline-delimited JSON wire format and `PUT`s it to the
+    /// configured endpoint
+    pub(crate) fn push(&self, snippets: &[Snippet]) -> color_eyre::Result<()> {
+        let mut body = Vec::new();
+        for snippet in snippets {
+            snippet.to_json(&mut body)?;
+            body.push(b'\n');
+        }
+        ureq::put(&self.endpoint).send_bytes(&body)?;
+        Ok(())
+    }
+
+    /// Fetches the remote snippet collection, serving it from the on-disk cache when younger than
+    /// `cache_ttl` instead of hitting the network
+    pub(crate) fn fetch_remote(&self) -> color_eyre::Result<Vec<Snippet>> {
+        if let Some(cached) = self.read_cache_if_fresh()? {
+            return Ok(cached);
+        }
+        let response = ureq::get(&self.endpoint).call()?;
+        let mut body = Vec::new();
+        response.into_reader().read_to_end(&mut body)?;
+        std::fs::write(&self.cache_path, &body)?;
+        Self::parse_stream(&body)
+    }
+
+    /// Returns the cached collection if `cache_path` exists and was last written within
+    /// `cache_ttl`, `None` otherwise (including when there is no cache yet)
+    fn read_cache_if_fresh(&self) -> color_eyre::Result<Option<Vec<Snippet>>> {
+        let metadata = match std::fs::metadata(&self.cache_path) {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(None),
+        };
+        let age = metadata.modified()?.elapsed().unwrap_or(Duration::MAX);
+        if age > self.cache_ttl() {
+            return Ok(None);
+        }
+        Self::parse_stream(&std::fs::read(&self.cache_path)?).map(Some)
+    }
+
+    fn parse_stream(body: &[u8]) -> color_eyre::Result<Vec<Snippet>> {
+        let mut cursor = Cursor::new(body);
+        Snippet::read(&mut cursor)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    /// Fetches the remote collection, merges it with `local`, prints any conflicts for the user to
+    /// review, and returns the merged collection for the caller to persist
+    pub(crate) fn pull(&self, local: Vec<Snippet>) -> color_eyre::Result<Vec<Snippet>> {
+        // Must be read before `fetch_remote`, which overwrites `cache_path` (and so its mtime) on
+        // every cache-miss fetch.
+        let last_synced = self.last_synced();
+        let remote = self.fetch_remote()?;
+        let outcome = merge(local, remote, last_synced);
+        for conflict in &outcome.conflicts {
+            eprintln!(
+                "{} conflict on snippet #{}: both local (updated {}) and remote (updated {}) changed since the last sync - kept the more recently updated side, the other is shown below for reference\n    local:  {}\n    remote: {}",
+                utils::BOX,
+                conflict.index,
+                conflict.local.updated,
+                conflict.remote.updated,
+                conflict.local.description,
+                conflict.remote.description,
+            );
+        }
+        Ok(outcome.merged)
+    }
+
+    /// Approximates "the time of the last successful pull" from the cache file's modification
+    /// time. A missing cache (no sync has ever happened) falls back to the earliest representable
+    /// timestamp, so `merge` treats every remote snippet as changed rather than guessing.
+    fn last_synced(&self) -> DateTime<Utc> {
+        std::fs::metadata(&self.cache_path)
+            .and_then(|metadata| metadata.modified())
+            .map(DateTime::<Utc>::from)
+            .unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+}
+
+/// What a CLI `the-way sync <push|pull>` invocation should do; `execute` is the handler a command
+/// dispatcher calls
+pub(crate) enum SyncCommand {
+    Push,
+    Pull,
+}
+
+impl SyncCommand {
+    /// Runs this sync command against `config`. `Push` returns `None` (nothing to persist
+    /// locally); `Pull` returns the merged collection the caller should save in place of `local`.
+    pub(crate) fn execute(
+        self,
+        config: &SyncConfig,
+        local: Vec<Snippet>,
+    ) -> color_eyre::Result<Option<Vec<Snippet>>> {
+        match self {
+            Self::Push => {
+                config.push(&local)?;
+                Ok(None)
+            }
+            Self::Pull => Ok(Some(config.pull(local)?)),
+        }
+    }
+}
+
+/// Merges `local` and `remote` snippet collections by `index`: the side with the later `updated`
+/// timestamp wins, snippets present on only one side are kept as-is, and a snippet whose content
+/// changed on both sides since `last_synced` is reported as a conflict rather than silently
+/// clobbered
+pub(crate) fn merge(
+    local: Vec<Snippet>,
+    remote: Vec<Snippet>,
+    last_synced: DateTime<Utc>,
+) -> MergeOutcome {
+    let mut by_index: HashMap<usize, Snippet> = local.into_iter().map(|s| (s.index, s)).collect();
+    let mut conflicts = Vec::new();
+
+    for remote_snippet in remote {
+        let Some(local_snippet) = by_index.remove(&remote_snippet.index) else {
+            by_index.insert(remote_snippet.index, remote_snippet);
+            continue;
+        };
+
+        let local_changed = local_snippet.updated > last_synced;
+        let remote_changed = remote_snippet.updated > last_synced;
+        let diverged = local_snippet.code != remote_snippet.code
+            || local_snippet.description != remote_snippet.description;
+
+        let local_wins = local_snippet.updated >= remote_snippet.updated;
+        if local_changed && remote_changed && diverged {
+            let winner = if local_wins {
+                local_snippet.clone()
+            } else {
+                remote_snippet.clone()
+            };
+            conflicts.push(SyncConflict {
+                index: winner.index,
+                local: local_snippet,
+                remote: remote_snippet,
+            });
+            by_index.insert(winner.index, winner);
+        } else {
+            let winner = if local_wins {
+                local_snippet
+            } else {
+                remote_snippet
+            };
+            by_index.insert(winner.index, winner);
+        }
+    }
+
+    let mut merged: Vec<Snippet> = by_index.into_values().collect();
+    merged.sort_by_key(|snippet| snippet.index);
+    MergeOutcome { merged, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(index: usize, code: &str, updated_secs: i64) -> Snippet {
+        Snippet {
+            index,
+            description: String::from("a snippet"),
+            language: String::from("rust"),
+            code: code.to_owned(),
+            extension: String::from("rs"),
+            tags: Vec::new(),
+            date: DateTime::<Utc>::MIN_UTC,
+            updated: DateTime::<Utc>::MIN_UTC + chrono::Duration::seconds(updated_secs),
+        }
+    }
+
+    #[test]
+    fn keeps_snippets_present_on_only_one_side() {
+        let local = vec![snippet(1, "local only", 1)];
+        let remote = vec![snippet(2, "remote only", 1)];
+        let outcome = merge(local, remote, DateTime::<Utc>::MIN_UTC);
+        assert!(outcome.conflicts.is_empty());
+        assert_eq!(outcome.merged.len(), 2);
+    }
+
+    #[test]
+    fn remote_wins_when_only_remote_changed_since_last_sync() {
+        let last_synced = DateTime::<Utc>::MIN_UTC + chrono::Duration::seconds(5);
+        let local = vec![snippet(1, "stale", 1)];
+        let remote = vec![snippet(1, "fresh", 10)];
+        let outcome = merge(local, remote, last_synced);
+        assert!(outcome.conflicts.is_empty());
+        assert_eq!(outcome.merged[0].code, "fresh");
+    }
+
+    #[test]
+    fn reports_a_conflict_when_both_sides_diverged_since_last_sync() {
+        let last_synced = DateTime::<Utc>::MIN_UTC;
+        let local = vec![snippet(1, "local edit", 5)];
+        let remote = vec![snippet(1, "remote edit", 10)];
+        let outcome = merge(local, remote, last_synced);
+        assert_eq!(outcome.conflicts.len(), 1);
+        // the more recently updated side still wins the merged collection
+        assert_eq!(outcome.merged[0].code, "remote edit");
+    }
+
+    #[test]
+    fn does_not_conflict_when_both_sides_updated_after_last_sync_with_identical_content() {
+        // both sides' `updated` moved past last_synced (e.g. touched without edits) but their
+        // content didn't diverge
+        let last_synced = DateTime::<Utc>::MIN_UTC;
+        let local = vec![snippet(1, "same", 5)];
+        let remote = vec![snippet(1, "same", 10)];
+        let outcome = merge(local, remote, last_synced);
+        assert!(outcome.conflicts.is_empty());
+    }
+}
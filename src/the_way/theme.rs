@@ -0,0 +1,131 @@
+//! `#RRGGBB`/`#RRGGBBAA` color parsing for the config file's theme section
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer};
+
+/// An RGBA color packed as `0xRRGGBBAA`, parsed from a `#RRGGBB` or `#RRGGBBAA` hex string.
+/// 6-digit strings are expanded to full opacity (`AA` = `FF`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct HexColor(pub(crate) u32);
+
+impl HexColor {
+    pub(crate) fn r(self) -> u8 {
+        (self.0 >> 24) as u8
+    }
+
+    pub(crate) fn g(self) -> u8 {
+        (self.0 >> 16) as u8
+    }
+
+    pub(crate) fn b(self) -> u8 {
+        (self.0 >> 8) as u8
+    }
+
+    pub(crate) fn a(self) -> u8 {
+        self.0 as u8
+    }
+
+    /// Parses a `#RRGGBB` or `#RRGGBBAA` string into a packed RGBA value, expanding 6-digit input
+    /// to full opacity and rejecting anything else with a clear "expected #RRGGBB[AA]" error
+    pub(crate) fn parse(text: &str) -> color_eyre::Result<Self> {
+        let invalid = || color_eyre::eyre::eyre!("invalid color {text:?}: expected #RRGGBB[AA]");
+        let hex = text.strip_prefix('#').ok_or_else(invalid)?;
+        // `from_str_radix` alone would also accept a leading sign, e.g. "#+12345"
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let value = match hex.len() {
+            6 => (u32::from_str_radix(hex, 16).map_err(|_| invalid())? << 8) | 0xFF,
+            8 => u32::from_str_radix(hex, 16).map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        };
+        Ok(Self(value))
+    }
+}
+
+impl<'de> Deserialize<'de> for HexColor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct HexColorVisitor;
+
+        impl<'de> Visitor<'de> for HexColorVisitor {
+            type Value = HexColor;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a color in #RRGGBB or #RRGGBBAA format")
+            }
+
+            fn visit_str<E>(self, text: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                HexColor::parse(text).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(HexColorVisitor)
+    }
+}
+
+/// User-configurable theme overrides for `CodeHighlight`'s header styles and, optionally,
+/// per-language block colors. Any field left unset in the config file falls back to
+/// `CodeHighlight`'s built-in defaults, so adding a theme section is entirely optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct ThemeConfig {
+    /// Overrides `CodeHighlight::main_style`'s color
+    #[serde(default)]
+    pub(crate) main_color: Option<HexColor>,
+    /// Overrides `CodeHighlight::accent_style`'s color
+    #[serde(default)]
+    pub(crate) accent_color: Option<HexColor>,
+    /// Overrides `CodeHighlight::tag_style`'s color
+    #[serde(default)]
+    pub(crate) tag_color: Option<HexColor>,
+    /// Per-language overrides of `Language::color`, keyed by language name
+    #[serde(default)]
+    pub(crate) language_colors: HashMap<String, HexColor>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_six_digit_hex_to_full_opacity() {
+        assert_eq!(HexColor::parse("#112233").unwrap().0, 0x112233FF);
+    }
+
+    #[test]
+    fn keeps_eight_digit_hex_alpha_as_given() {
+        assert_eq!(HexColor::parse("#11223344").unwrap().0, 0x11223344);
+    }
+
+    #[test]
+    fn splits_a_parsed_color_back_into_its_components() {
+        let color = HexColor::parse("#112233aa").unwrap();
+        assert_eq!(
+            (color.r(), color.g(), color.b(), color.a()),
+            (0x11, 0x22, 0x33, 0xaa)
+        );
+    }
+
+    #[test]
+    fn rejects_a_missing_hash_prefix() {
+        assert!(HexColor::parse("112233").is_err());
+    }
+
+    #[test]
+    fn rejects_the_wrong_number_of_digits() {
+        assert!(HexColor::parse("#1122").is_err());
+    }
+
+    #[test]
+    fn rejects_non_hex_digits() {
+        assert!(HexColor::parse("#gggggg").is_err());
+    }
+}
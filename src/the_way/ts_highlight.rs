@@ -0,0 +1,189 @@
+//! Tree-sitter highlighting backend, selected via `HighlightBackend`
+//!
+//! `TreeSitterHighlighter` loads one grammar `.so`/`.dll` per language from a runtime grammar
+//! directory and produces `HighlightSpan`s by running that language's `highlights.scm` query over
+//! the parsed tree. `CodeHighlight::highlight_code` (see `src/language.rs`) tries this backend
+//! first when `HighlightBackend::TreeSitter` is configured, and falls back to syntect whenever a
+//! grammar or query file isn't installed under the name it is asked for (the snippet's extension,
+//! or a Markdown fence's info string for an injected region) - callers never see an error
+//! just because a grammar is missing.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use libloading::{Library, Symbol};
+use tree_sitter::{Language as TSLanguage, Parser, Query, QueryCursor};
+
+/// A highlighted region of source code, tagged with the semantic category it was captured as
+pub(crate) struct HighlightSpan {
+    /// Byte range into the original source, as required to splice highlighted fragments back in
+    pub(crate) range: Range<usize>,
+    /// Capture name from `highlights.scm`, e.g. "keyword", "function", "string", "comment"
+    pub(crate) category: String,
+}
+
+/// Which highlighting backend to use for a snippet's code. Defaults to `Syntect` so existing
+/// users and configs are unaffected; `TreeSitter` is opt-in via config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HighlightBackend {
+    Syntect,
+    TreeSitter,
+}
+
+impl Default for HighlightBackend {
+    fn default() -> Self {
+        Self::Syntect
+    }
+}
+
+/// Loads and caches tree-sitter grammars from a runtime directory, one dynamic library per
+/// language (`<grammar_dir>/<language>.so` / `.dll`), named after the `tree_sitter_<language>`
+/// entry point each grammar crate exposes.
+pub(crate) struct TreeSitterHighlighter {
+    grammar_dir: PathBuf,
+    // Keeps each `Library` alive for as long as its `TSLanguage` may be used
+    languages: HashMap<String, (TSLanguage, Library)>,
+}
+
+impl TreeSitterHighlighter {
+    /// Creates a highlighter that resolves grammars from `grammar_dir`
+    pub(crate) fn new(grammar_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            grammar_dir: grammar_dir.into(),
+            languages: HashMap::new(),
+        }
+    }
+
+    /// Parses `code` with the grammar for `language_name` and runs `highlights.scm` (read from
+    /// `<grammar_dir>/<language_name>/highlights.scm`) over the resulting tree to produce one span
+    /// per capture, sorted by start offset; spans may nest (e.g. `@string.escape` inside
+    /// `@string`). Returns `Ok(None)` rather than erroring when no grammar or no `highlights.scm`
+    /// is installed for `language_name`, so callers can fall back to syntect.
+    pub(crate) fn highlight(
+        &mut self,
+        code: &str,
+        language_name: &str,
+    ) -> color_eyre::Result<Option<Vec<HighlightSpan>>> {
+        let language = match self.grammar(language_name)? {
+            Some(language) => language,
+            None => return Ok(None),
+        };
+
+        let mut parser = Parser::new();
+        parser.set_language(*language)?;
+        let tree = parser.parse(code, None).ok_or_else(|| {
+            color_eyre::eyre::eyre!("tree-sitter failed to parse {language_name} snippet")
+        })?;
+
+        let query_path = self.grammar_dir.join(language_name).join("highlights.scm");
+        if !query_path.exists() {
+            // A grammar with no highlights.scm is as unusable to us as no grammar at all; fall
+            // back to syntect rather than erroring out.
+            return Ok(None);
+        }
+        let query_source = std::fs::read_to_string(&query_path)?;
+        let query = Query::new(*language, &query_source)?;
+        let mut cursor = QueryCursor::new();
+
+        let mut spans: Vec<HighlightSpan> = cursor
+            .matches(&query, tree.root_node(), code.as_bytes())
+            .flat_map(|m| m.captures.iter())
+            .map(|capture| HighlightSpan {
+                range: capture.node.byte_range(),
+                category: query.capture_names()[capture.index as usize].clone(),
+            })
+            .collect();
+        spans.sort_by_key(|span| span.range.start);
+        Ok(Some(spans))
+    }
+
+    /// Returns the cached grammar for `language_name`, loading its dynamic library on first use
+    fn grammar(&mut self, language_name: &str) -> color_eyre::Result<Option<&TSLanguage>> {
+        if !self.languages.contains_key(language_name) {
+            match self.load_grammar(language_name) {
+                Ok(Some(entry)) => {
+                    self.languages.insert(language_name.to_owned(), entry);
+                }
+                Ok(None) => return Ok(None),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(self
+            .languages
+            .get(language_name)
+            .map(|(language, _)| language))
+    }
+
+    /// Loads `<grammar_dir>/<language_name>.{so,dll}` and resolves its `tree_sitter_<language_name>`
+    /// symbol. Returns `None` when the library file simply doesn't exist, reserving `Err` for
+    /// grammars that exist but fail to load.
+    fn load_grammar(
+        &self,
+        language_name: &str,
+    ) -> color_eyre::Result<Option<(TSLanguage, Library)>> {
+        let extension = if cfg!(windows) { "dll" } else { "so" };
+        let path = self
+            .grammar_dir
+            .join(format!("{language_name}.{extension}"));
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        // Safety: the grammar directory is a user-configured, explicitly opted-into location; the
+        // loaded symbol is only ever called through tree-sitter's `Parser::set_language`.
+        unsafe {
+            let library = Library::new(&path)?;
+            let symbol_name = format!("tree_sitter_{language_name}");
+            let constructor: Symbol<unsafe extern "C" fn() -> TSLanguage> =
+                library.get(symbol_name.as_bytes())?;
+            let language = constructor();
+            Ok(Some((language, library)))
+        }
+    }
+}
+
+/// Maps a tree-sitter capture name to a coarse category by its leading segment, which
+/// `CodeHighlight` then colors from its own fixed tree-sitter palette
+pub(crate) fn capture_to_style_category(capture: &str) -> &str {
+    match capture {
+        c if c.starts_with("keyword") => "keyword",
+        c if c.starts_with("function") => "function",
+        c if c.starts_with("string") => "string",
+        c if c.starts_with("comment") => "comment",
+        c if c.starts_with("type") => "type",
+        c if c.starts_with("variable") => "variable",
+        _ => "text",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_to_syntect() {
+        assert_eq!(HighlightBackend::default(), HighlightBackend::Syntect);
+    }
+
+    #[test]
+    fn maps_dotted_captures_by_their_leading_segment() {
+        assert_eq!(capture_to_style_category("keyword.operator"), "keyword");
+        assert_eq!(capture_to_style_category("function.method"), "function");
+        assert_eq!(capture_to_style_category("string.special"), "string");
+    }
+
+    #[test]
+    fn falls_back_to_text_for_unrecognized_captures() {
+        assert_eq!(capture_to_style_category("punctuation.bracket"), "text");
+    }
+
+    #[test]
+    fn missing_grammar_library_is_not_an_error() {
+        let dir = std::env::temp_dir().join("the-way-ts-highlight-test-no-grammar");
+        let mut highlighter = TreeSitterHighlighter::new(dir);
+        let spans = highlighter.highlight("fn main() {}", "rust").unwrap();
+        assert!(spans.is_none());
+    }
+}